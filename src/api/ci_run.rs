@@ -1,36 +1,166 @@
 // src/api/ci_run.rs
-use axum::{extract::State, Json, response::IntoResponse};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    response::IntoResponse,
+    Json,
+};
 use serde::Deserialize;
-use futures_util::stream::{self};
 use axum::body::Body;
+use std::convert::Infallible;
 use std::time::Duration;
 use time::OffsetDateTime;
-use tokio_stream::StreamExt;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
-use crate::{config::AppConfig, entitlements::Entitlements};
+use crate::{config::AppConfig, docker::DockerClient, entitlements::Entitlements, reconcile};
 
 #[derive(Deserialize)]
 pub struct CiRunRequest {
     pub mode: String,
 }
 
+/// Validates `mode` and, if valid, spawns the plan (and, in apply mode,
+/// the apply) as a background task that sends each NDJSON-shaped event
+/// down `tx` as `reconcile::plan`/`apply_change` actually produce it —
+/// not after collecting everything into a buffer. Both the NDJSON and
+/// SSE handlers below just forward whatever arrives on the returned
+/// stream, so a client sees `started`/`done` lines exactly when the
+/// underlying `docker compose` invocations complete, not as a post-hoc
+/// replay.
+fn spawn_run(cfg: AppConfig, ents: Entitlements, req: CiRunRequest) -> Result<ReceiverStream<serde_json::Value>, (StatusCode, String)> {
+    let apply = match req.mode.as_str() {
+        "plan" | "dry-run" => false,
+        "apply" => true,
+        other => return Err((StatusCode::BAD_REQUEST, format!("unknown mode '{other}'"))),
+    };
+
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(async move {
+        let start = OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_else(|_| "now".into());
+        let _ = tx
+            .send(serde_json::json!({
+                "ts": start,
+                "level": "info",
+                "msg": "run started",
+                "edition": ents.edition,
+                "mode": req.mode,
+            }))
+            .await;
+
+        let docker = DockerClient::new(cfg.docker_host.clone());
+        let (changes, mut summary) = reconcile::plan(&cfg, &docker, &tx).await;
+
+        if changes.is_empty() {
+            let _ = tx.send(serde_json::json!({"level": "info", "msg": "nothing to change"})).await;
+        }
+
+        if apply {
+            for change in &changes {
+                let _ = tx
+                    .send(serde_json::json!({
+                        "level": "info",
+                        "stack": change.stack,
+                        "action": change.action,
+                        "status": "started",
+                    }))
+                    .await;
+                match reconcile::apply_change(&cfg, change).await {
+                    Ok(()) => {
+                        let _ = tx
+                            .send(serde_json::json!({
+                                "level": "info",
+                                "stack": change.stack,
+                                "action": change.action,
+                                "status": "done",
+                            }))
+                            .await;
+                    }
+                    Err(err) => {
+                        summary.failed += 1;
+                        let _ = tx
+                            .send(serde_json::json!({
+                                "level": "error",
+                                "stack": change.stack,
+                                "action": change.action,
+                                "status": "failed",
+                                "error": err.to_string(),
+                            }))
+                            .await;
+                    }
+                }
+            }
+        }
+
+        let _ = tx
+            .send(serde_json::json!({"level": "done", "summary": {
+                "hosts": summary.hosts,
+                "stacks": summary.stacks,
+                "changed": summary.changed,
+                "failed": summary.failed,
+            }}))
+            .await;
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+/// `POST /api/ci/run` — NDJSON transport for CLI consumers.
 pub async fn ci_run(
-    State(_cfg): State<AppConfig>,
+    State(cfg): State<AppConfig>,
     State(ents): State<Entitlements>,
-    Json(_req): Json<CiRunRequest>,
+    Json(req): Json<CiRunRequest>,
 ) -> impl IntoResponse {
-    let start = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_else(|_| "now".into());
-    let lines = vec![
-        serde_json::json!({"ts": start, "level": "info", "msg": "run started", "edition": ents.edition}),
-        serde_json::json!({"level": "info", "msg": "planning"}),
-        serde_json::json!({"level": "info", "msg": "nothing to change"}),
-        serde_json::json!({"level": "done", "summary": {"hosts": 0, "stacks": 0, "changed": 0, "failed": 0}}),
-    ];
-    let stream = stream::iter(lines.into_iter())
-        .throttle(Duration::from_millis(200))
-        .map(|v| Ok::<_, std::io::Error>(format!("{}\n", serde_json::to_string(&v).unwrap())));
+    if !ents.features.ci_api {
+        return (StatusCode::FORBIDDEN, "ci_api is not enabled for this edition").into_response();
+    }
+
+    let stream = match spawn_run(cfg, ents, req) {
+        Ok(stream) => stream,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+
+    let body_stream = stream.map(|v| Ok::<_, std::io::Error>(format!("{}\n", serde_json::to_string(&v).unwrap())));
     axum::http::Response::builder()
         .header("Content-Type", "application/x-ndjson")
-        .body(Body::from_stream(stream))
+        .body(Body::from_stream(body_stream))
         .unwrap()
-}
\ No newline at end of file
+        .into_response()
+}
+
+/// `GET /api/ci/run/stream` — SSE transport for browser `EventSource`
+/// clients, which can't consume NDJSON-over-fetch cleanly and need
+/// auto-reconnect plus keep-alive through idling proxies. Because each
+/// event is forwarded as soon as the background run task sends it, the
+/// `KeepAlive` interval now genuinely covers real idle gaps between
+/// `docker compose` steps rather than racing a buffered replay.
+pub async fn ci_run_stream(
+    State(cfg): State<AppConfig>,
+    State(ents): State<Entitlements>,
+    Query(req): Query<CiRunRequest>,
+) -> impl IntoResponse {
+    if !ents.features.ci_api {
+        return (StatusCode::FORBIDDEN, "ci_api is not enabled for this edition").into_response();
+    }
+
+    let keepalive = Duration::from_secs(cfg.ci_stream_keepalive_secs);
+    let stream = match spawn_run(cfg, ents, req) {
+        Ok(stream) => stream,
+        Err((status, msg)) => return (status, msg).into_response(),
+    };
+
+    // Each event carries a per-run sequential id (starting at 0). This
+    // handler does not read `Last-Event-ID`: a reconnecting `EventSource`
+    // issues a new request, which starts a new run from "run started".
+    let sse_stream = futures_util::StreamExt::enumerate(stream).map(|(id, v)| {
+        let kind = if v.get("level").and_then(|l| l.as_str()) == Some("done") { "summary" } else { "log" };
+        Ok::<_, Infallible>(Event::default().id(id.to_string()).event(kind).json_data(v).unwrap())
+    });
+
+    Sse::new(sse_stream)
+        .keep_alive(KeepAlive::new().interval(keepalive))
+        .into_response()
+}
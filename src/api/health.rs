@@ -2,10 +2,19 @@
 use axum::{Json, extract::State};
 use serde::Serialize;
 use crate::entitlements::Entitlements;
+use crate::repo_scan::RepoSha;
 
 #[derive(Serialize)]
-pub struct Health { status: &'static str, edition: String }
+pub struct Health {
+    status: &'static str,
+    edition: String,
+    repo_commit: Option<String>,
+}
 
-pub async fn healthz(State(ents): State<Entitlements>) -> Json<Health> {
-    Json(Health { status: "ok", edition: ents.edition })
+pub async fn healthz(State(ents): State<Entitlements>, State(repo_sha): State<RepoSha>) -> Json<Health> {
+    Json(Health {
+        status: "ok",
+        edition: ents.edition,
+        repo_commit: (*repo_sha.load_full()).clone(),
+    })
 }
\ No newline at end of file
@@ -3,12 +3,28 @@ use serde::{Serialize, Deserialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 use crate::config::AppConfig;
+use crate::docker::DockerClient;
+use crate::repo_scan;
+use crate::sops::{self, DecryptStatus};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Container {
     pub name: String,
     pub image: String,
     pub state: String,
+    /// The `com.docker.compose.service` label value, i.e. the bare
+    /// service name from the compose file (distinct from `name`, which
+    /// is Docker's generated `<project>-<service>-<n>` container name).
+    pub service: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Service {
+    pub name: String,
+    pub mode: String,
+    pub replicas_running: u64,
+    pub replicas_desired: u64,
+    pub image: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -17,7 +33,10 @@ pub struct Stack {
     pub r#type: String,
     pub path: String,
     pub sops: bool,
+    pub sops_status: DecryptStatus,
     pub containers: Vec<Container>,
+    #[serde(default)]
+    pub services: Vec<Service>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,8 +50,14 @@ pub struct Host {
 pub struct Inventory { pub hosts: Vec<Host> }
 
 pub async fn get_inventory(State(cfg): State<AppConfig>) -> Json<Inventory> {
+    let docker = DockerClient::new(cfg.docker_host.clone());
     let mut hosts: Vec<Host> = Vec::new();
-    let root = PathBuf::from(&cfg.scan_root);
+    let root = if cfg.scan_kind == "repo" {
+        repo_scan::worktree_path(&cfg)
+    } else {
+        PathBuf::from(&cfg.scan_root)
+    };
+    let swarm_manager = docker.is_swarm_manager().await;
     if root.exists() {
         if let Ok(host_dirs) = fs::read_dir(&root) {
             for host_entry in host_dirs.flatten() {
@@ -47,12 +72,25 @@ pub async fn get_inventory(State(cfg): State<AppConfig>) -> Json<Inventory> {
                         let dc_tpl = stack_entry.path().join("docker-compose.tpl.yaml");
                         let rtype = if dc_yaml.exists() || dc_tpl.exists() { "compose" } else { "script" }.to_string();
                         let sops = glob_has_sops(&stack_entry.path());
+                        let sops_status = sops::check_stack_status(&cfg, &stack_entry.path());
+                        let containers = if rtype == "compose" {
+                            docker.containers_for_stack(&stack_name).await
+                        } else {
+                            vec![]
+                        };
+                        let services = if rtype == "compose" && swarm_manager {
+                            docker.services_for_stack(&stack_name).await
+                        } else {
+                            vec![]
+                        };
                         stacks.push(Stack {
                             name: stack_name,
                             r#type: rtype,
                             path: stack_entry.path().to_string_lossy().to_string(),
                             sops,
-                            containers: vec![],
+                            sops_status,
+                            containers,
+                            services,
                         });
                     }
                 }
@@ -1,23 +1,39 @@
 use std::sync::Arc;
+use arc_swap::ArcSwap;
 use axum::extract::FromRef;
 
-use crate::{config::AppConfig, entitlements::Entitlements};
+use crate::{config::AppConfig, entitlements::Entitlements, repo_scan::RepoSha};
 
+/// Holds the live config/entitlements snapshots. `ents` is swapped
+/// atomically by the hot-reload task in `reload.rs`; `cfg` is not
+/// swapped by anything and keeps the value read at startup. Handlers
+/// read whatever snapshot is current at the time of their request via
+/// the `FromRef` impls below.
 #[derive(Clone)]
 pub struct AppState {
-    pub cfg: AppConfig,
-    pub ents: Entitlements,
+    pub cfg: Arc<ArcSwap<AppConfig>>,
+    pub ents: Arc<ArcSwap<Entitlements>>,
+    pub repo_sha: RepoSha,
 }
 
 impl AppState {
-    pub fn new(cfg: AppConfig, ents: Entitlements) -> Self { Self { cfg, ents } }
+    pub fn new(cfg: AppConfig, ents: Entitlements) -> Self {
+        Self {
+            cfg: Arc::new(ArcSwap::from_pointee(cfg)),
+            ents: Arc::new(ArcSwap::from_pointee(ents)),
+            repo_sha: Arc::new(ArcSwap::from_pointee(None)),
+        }
+    }
 }
 
 impl FromRef<AppState> for AppConfig {
-    fn from_ref(s: &AppState) -> AppConfig { s.cfg.clone() }
+    fn from_ref(s: &AppState) -> AppConfig { (**s.cfg.load()).clone() }
 }
 impl FromRef<AppState> for Entitlements {
-    fn from_ref(s: &AppState) -> Entitlements { s.ents.clone() }
+    fn from_ref(s: &AppState) -> Entitlements { (**s.ents.load()).clone() }
+}
+impl FromRef<AppState> for RepoSha {
+    fn from_ref(s: &AppState) -> RepoSha { s.repo_sha.clone() }
 }
 
 pub mod health;
@@ -8,6 +8,15 @@ pub struct AppConfig {
     pub refresh_interval: String,
     pub license_env: String,
     pub license_path: String,
+    pub docker_host: String,
+    pub ci_stream_keepalive_secs: u64,
+    pub scan_ref: String,
+    pub scan_cache_dir: String,
+    pub scan_git_token: Option<String>,
+    pub scan_git_ssh_key: Option<String>,
+    pub sops_enable: bool,
+    pub sops_age_key: Option<String>,
+    pub sops_age_key_file: Option<String>,
 }
 
 impl AppConfig {
@@ -18,6 +27,20 @@ impl AppConfig {
         let refresh_interval = std::env::var("DDUI_REFRESH_INTERVAL").unwrap_or_else(|_| "10m".to_string());
         let license_env = std::env::var("DDUI_LICENSE_ENV").unwrap_or_else(|_| "DDUI_LICENSE".to_string());
         let license_path = std::env::var("DDUI_LICENSE_PATH").unwrap_or_else(|_| "/run/secrets/ddui_license".to_string());
+        let docker_host = std::env::var("DDUI_DOCKER_HOST").unwrap_or_else(|_| "unix:///var/run/docker.sock".to_string());
+        let ci_stream_keepalive_secs = std::env::var("DDUI_CI_STREAM_KEEPALIVE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(15);
+        let scan_ref = std::env::var("DDUI_SCAN_REF").unwrap_or_else(|_| "main".to_string());
+        let scan_cache_dir = std::env::var("DDUI_SCAN_CACHE_DIR").unwrap_or_else(|_| "/var/cache/ddui/repo".to_string());
+        let scan_git_token = std::env::var("DDUI_SCAN_GIT_TOKEN").ok();
+        let scan_git_ssh_key = std::env::var("DDUI_SCAN_GIT_SSH_KEY").ok();
+        let sops_enable = std::env::var("DDUI_SOPS_ENABLE")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let sops_age_key = std::env::var("DDUI_SOPS_AGE_KEY").ok();
+        let sops_age_key_file = std::env::var("DDUI_SOPS_AGE_KEY_FILE").ok();
 
         if scan_kind != "local" && scan_kind != "repo" {
             return Err(anyhow!("DDUI_SCAN_KIND must be 'local' or 'repo'"));
@@ -30,6 +53,15 @@ impl AppConfig {
             refresh_interval,
             license_env,
             license_path,
+            docker_host,
+            ci_stream_keepalive_secs,
+            scan_ref,
+            scan_cache_dir,
+            scan_git_token,
+            scan_git_ssh_key,
+            sops_enable,
+            sops_age_key,
+            sops_age_key_file,
         })
     }
 }
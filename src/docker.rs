@@ -0,0 +1,293 @@
+// src/docker.rs
+//! Minimal Docker Engine API client used to enrich inventory scans with
+//! live container state. Talks to the Unix socket by default, or a TCP
+//! host when `DDUI_DOCKER_HOST` points at one (e.g. `tcp://127.0.0.1:2375`).
+
+use http_body_util::BodyExt;
+use hyper::body::Bytes;
+use hyper::{Method, Request};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use hyperlocal::UnixClientExt;
+use serde::Deserialize;
+
+use crate::api::inventory::{Container, Service};
+
+#[derive(Clone)]
+pub struct DockerClient {
+    host: String,
+}
+
+#[derive(Deserialize)]
+struct ContainerSummary {
+    #[serde(default)]
+    #[serde(rename = "Names")]
+    names: Vec<String>,
+    #[serde(default, rename = "Image")]
+    image: String,
+    #[serde(default, rename = "State")]
+    state: String,
+    #[serde(default, rename = "Labels")]
+    labels: std::collections::HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct InfoResponse {
+    #[serde(rename = "Swarm")]
+    swarm: SwarmInfo,
+}
+
+#[derive(Deserialize)]
+struct SwarmInfo {
+    #[serde(rename = "LocalNodeState")]
+    local_node_state: String,
+    #[serde(default, rename = "ControlAvailable")]
+    control_available: bool,
+}
+
+#[derive(Deserialize)]
+struct ServiceSummary {
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(rename = "Spec")]
+    spec: ServiceSpec,
+}
+
+#[derive(Deserialize)]
+struct ServiceSpec {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Mode")]
+    mode: ServiceMode,
+    #[serde(rename = "TaskTemplate")]
+    task_template: TaskTemplate,
+}
+
+#[derive(Deserialize)]
+struct TaskTemplate {
+    #[serde(rename = "ContainerSpec")]
+    container_spec: ContainerSpec,
+}
+
+#[derive(Deserialize)]
+struct ContainerSpec {
+    #[serde(rename = "Image")]
+    image: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+enum ServiceMode {
+    Replicated(ReplicatedMode),
+    Global(GlobalMode),
+}
+
+#[derive(Deserialize)]
+struct GlobalMode {}
+
+#[derive(Deserialize)]
+struct ReplicatedMode {
+    #[serde(rename = "Replicas")]
+    replicas: u64,
+}
+
+#[derive(Deserialize)]
+struct TaskSummary {
+    #[serde(rename = "ServiceID")]
+    service_id: String,
+    #[serde(rename = "Status")]
+    status: TaskStatus,
+}
+
+#[derive(Deserialize)]
+struct TaskStatus {
+    #[serde(rename = "State")]
+    state: String,
+}
+
+impl DockerClient {
+    /// `host` is the raw `DDUI_DOCKER_HOST` value, e.g.
+    /// `unix:///var/run/docker.sock` or `tcp://127.0.0.1:2375`.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self { host: host.into() }
+    }
+
+    /// Lists containers labeled as belonging to `stack` (the compose
+    /// project name). Returns an empty vec rather than an error when the
+    /// daemon is unreachable, so callers degrade to a local-only scan.
+    pub async fn containers_for_stack(&self, stack: &str) -> Vec<Container> {
+        match self.list_containers(stack).await {
+            Ok(containers) => containers,
+            Err(err) => {
+                tracing::warn!(%stack, error = %err, "docker engine unreachable, leaving containers empty");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn list_containers(&self, stack: &str) -> anyhow::Result<Vec<Container>> {
+        let filters = serde_json::json!({ "label": [format!("com.docker.compose.project={stack}")] });
+        let path = format!(
+            "/containers/json?all=1&filters={}",
+            urlencoding::encode(&filters.to_string())
+        );
+        let body = self.get(&path).await?;
+        let summaries: Vec<ContainerSummary> = serde_json::from_slice(&body)?;
+        Ok(summaries
+            .into_iter()
+            .map(|c| {
+                let service = c
+                    .labels
+                    .get("com.docker.compose.service")
+                    .cloned()
+                    .unwrap_or_default();
+                Container {
+                    name: c
+                        .names
+                        .into_iter()
+                        .next()
+                        .unwrap_or_default()
+                        .trim_start_matches('/')
+                        .to_string(),
+                    image: c.image,
+                    state: c.state,
+                    service,
+                }
+            })
+            .collect())
+    }
+
+    /// Reports whether this node is an active Swarm manager, per
+    /// `GET /info`'s `Swarm.LocalNodeState`. Returns `false` (rather than
+    /// erroring) when the daemon is unreachable or not in swarm mode, so
+    /// standalone-compose hosts simply never populate `services`.
+    pub async fn is_swarm_manager(&self) -> bool {
+        match self.info().await {
+            Ok(info) => info.swarm.local_node_state == "active" && info.swarm.control_available,
+            Err(err) => {
+                tracing::debug!(error = %err, "docker engine unreachable, assuming not a swarm manager");
+                false
+            }
+        }
+    }
+
+    async fn info(&self) -> anyhow::Result<InfoResponse> {
+        let body = self.get("/info").await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Lists swarm services (and their task replica counts) for stacks
+    /// deployed with `com.docker.stack.namespace=<stack>`. Empty when the
+    /// node isn't a swarm manager or the daemon is unreachable.
+    pub async fn services_for_stack(&self, stack: &str) -> Vec<Service> {
+        match self.list_services(stack).await {
+            Ok(services) => services,
+            Err(err) => {
+                tracing::warn!(%stack, error = %err, "failed to list swarm services, leaving services empty");
+                Vec::new()
+            }
+        }
+    }
+
+    async fn list_services(&self, stack: &str) -> anyhow::Result<Vec<Service>> {
+        let filters = serde_json::json!({ "label": [format!("com.docker.stack.namespace={stack}")] });
+        let services_path = format!("/services?filters={}", urlencoding::encode(&filters.to_string()));
+        let body = self.get(&services_path).await?;
+        let summaries: Vec<ServiceSummary> = serde_json::from_slice(&body)?;
+
+        let tasks_path = format!("/tasks?filters={}", urlencoding::encode(&filters.to_string()));
+        let tasks_body = self.get(&tasks_path).await?;
+        let tasks: Vec<TaskSummary> = serde_json::from_slice(&tasks_body).unwrap_or_else(|err| {
+            tracing::warn!(%stack, error = %err, "failed to decode /tasks response, reporting 0 replicas running");
+            Vec::new()
+        });
+
+        Ok(summaries
+            .into_iter()
+            .map(|s| {
+                let running = tasks
+                    .iter()
+                    .filter(|t| t.service_id == s.id && t.status.state == "running")
+                    .count() as u64;
+                let (mode, desired) = match &s.spec.mode {
+                    ServiceMode::Replicated(replicated) => ("replicated".to_string(), replicated.replicas),
+                    ServiceMode::Global(_) => ("global".to_string(), running),
+                };
+                Service {
+                    name: s.spec.name,
+                    mode,
+                    replicas_running: running,
+                    replicas_desired: desired,
+                    image: s.spec.task_template.container_spec.image,
+                }
+            })
+            .collect())
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<Bytes> {
+        if let Some(socket_path) = self.host.strip_prefix("unix://") {
+            let uri: hyper::Uri = hyperlocal::Uri::new(socket_path, path).into();
+            let client = Client::unix();
+            let resp = client.get(uri).await?;
+            Ok(resp.into_body().collect().await?.to_bytes())
+        } else {
+            let base = self
+                .host
+                .replacen("tcp://", "http://", 1);
+            let uri: hyper::Uri = format!("{base}{path}").parse()?;
+            let client: Client<_, axum::body::Body> =
+                Client::builder(TokioExecutor::new()).build_http();
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(axum::body::Body::empty())?;
+            let resp = client.request(req).await?;
+            Ok(resp.into_body().collect().await?.to_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_mode_deserializes_real_engine_shape() {
+        let replicated: ServiceMode = serde_json::from_str(r#"{"Replicated":{"Replicas":3}}"#).unwrap();
+        match replicated {
+            ServiceMode::Replicated(r) => assert_eq!(r.replicas, 3),
+            ServiceMode::Global(_) => panic!("expected replicated"),
+        }
+
+        let global: ServiceMode = serde_json::from_str(r#"{"Global":{}}"#).unwrap();
+        assert!(matches!(global, ServiceMode::Global(_)));
+    }
+
+    #[test]
+    fn container_summary_deserializes_labels() {
+        let json = r#"[{
+            "Names": ["/myproj-web-1"],
+            "Image": "nginx:1.27",
+            "State": "running",
+            "Labels": {"com.docker.compose.service": "web", "com.docker.compose.project": "myproj"}
+        }]"#;
+        let summaries: Vec<ContainerSummary> = serde_json::from_str(json).unwrap();
+        assert_eq!(summaries[0].labels.get("com.docker.compose.service"), Some(&"web".to_string()));
+    }
+
+    #[test]
+    fn service_summary_deserializes_real_engine_shape() {
+        let json = r#"{
+            "ID": "svc1",
+            "Spec": {
+                "Name": "web",
+                "Mode": {"Replicated": {"Replicas": 2}},
+                "TaskTemplate": {"ContainerSpec": {"Image": "nginx:1.27"}}
+            }
+        }"#;
+        let summary: ServiceSummary = serde_json::from_str(json).unwrap();
+        assert_eq!(summary.id, "svc1");
+        assert_eq!(summary.spec.name, "web");
+        assert_eq!(summary.spec.task_template.container_spec.image, "nginx:1.27");
+    }
+}
@@ -1,5 +1,10 @@
 mod config;
 mod entitlements;
+mod docker;
+mod reconcile;
+mod reload;
+mod repo_scan;
+mod sops;
 mod api;
 
 use axum::{routing::{get, post}, Router};
@@ -17,11 +22,14 @@ async fn main() -> anyhow::Result<()> {
     let cfg = config::AppConfig::from_env()?;
     let ents = entitlements::Entitlements::load()?;
     let shared = api::AppState::new(cfg.clone(), ents.clone());
+    reload::spawn(shared.clone());
+    repo_scan::spawn(shared.cfg.clone(), shared.repo_sha.clone());
 
     let app = Router::new()
         .route("/api/healthz", get(api::health::healthz))
         .route("/api/inventory", get(api::inventory::get_inventory))
         .route("/api/ci/run", post(api::ci_run::ci_run))
+        .route("/api/ci/run/stream", get(api::ci_run::ci_run_stream))
         .with_state(shared)
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http());
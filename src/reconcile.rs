@@ -0,0 +1,281 @@
+// src/reconcile.rs
+//! Compose reconciler: walks the same host/stack tree `inventory.rs`
+//! scans, renders each stack's compose file, diffs the desired service
+//! set against what the Docker daemon actually has running, and (in
+//! apply mode) drives `docker compose` to close the gap.
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::api::inventory::Container;
+use crate::config::AppConfig;
+use crate::docker::DockerClient;
+use crate::sops::{self, DecryptStatus};
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Create,
+    Recreate,
+    Remove,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlannedChange {
+    pub host: String,
+    pub stack: String,
+    pub service: String,
+    pub action: Action,
+    pub path: String,
+}
+
+#[derive(Default)]
+pub struct PlanSummary {
+    pub hosts: usize,
+    pub stacks: usize,
+    pub changed: usize,
+    pub failed: usize,
+}
+
+/// Walks the scan tree (`cfg.scan_root`, or the checked-out worktree in
+/// `repo` mode, as `inventory.rs` does) and computes, per stack, the services that need
+/// to be created, recreated (drifted), or removed relative to the
+/// compose file on disk. Does not touch anything. Emits a "planned"
+/// event on `events` for each change as soon as it's discovered, so a
+/// caller streaming the run to a client (see `api::ci_run`) can show
+/// progress while a large tree is still being walked instead of only
+/// after the whole scan finishes.
+pub async fn plan(
+    cfg: &AppConfig,
+    docker: &DockerClient,
+    events: &tokio::sync::mpsc::Sender<serde_json::Value>,
+) -> (Vec<PlannedChange>, PlanSummary) {
+    let mut changes = Vec::new();
+    let mut summary = PlanSummary::default();
+    let root = if cfg.scan_kind == "repo" {
+        crate::repo_scan::worktree_path(cfg)
+    } else {
+        PathBuf::from(&cfg.scan_root)
+    };
+    if !root.exists() {
+        return (changes, summary);
+    }
+    let Ok(host_dirs) = fs::read_dir(&root) else { return (changes, summary) };
+    for host_entry in host_dirs.flatten() {
+        if !host_entry.path().is_dir() { continue; }
+        summary.hosts += 1;
+        let host_name = host_entry.file_name().to_string_lossy().to_string();
+        let Ok(stack_dirs) = fs::read_dir(host_entry.path()) else { continue };
+        for stack_entry in stack_dirs.flatten() {
+            let stack_path = stack_entry.path();
+            if !stack_path.is_dir() { continue; }
+            let stack_name = stack_entry.file_name().to_string_lossy().to_string();
+            let Some(compose_path) = resolve_compose_file(&stack_path) else { continue };
+            summary.stacks += 1;
+            let desired = match desired_services(&compose_path) {
+                Ok(services) => services,
+                Err(err) => {
+                    tracing::warn!(stack = %stack_name, error = %err, "failed to render compose file");
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+            let running = docker.containers_for_stack(&stack_name).await;
+            let path_str = compose_path.to_string_lossy().to_string();
+            for (service, action) in diff_services(&desired, &running) {
+                summary.changed += 1;
+                let change = PlannedChange {
+                    host: host_name.clone(),
+                    stack: stack_name.clone(),
+                    service,
+                    action,
+                    path: path_str.clone(),
+                };
+                let _ = events
+                    .send(serde_json::json!({
+                        "level": "info",
+                        "stack": change.stack,
+                        "host": change.host,
+                        "service": change.service,
+                        "action": change.action,
+                        "status": "planned",
+                    }))
+                    .await;
+                changes.push(change);
+            }
+        }
+    }
+    (changes, summary)
+}
+
+/// Invokes `docker compose up -d --force-recreate` (create/recreate) or
+/// `docker compose rm -f -s` (remove) for a single planned change. When
+/// the stack has a decryptable SOPS file, its cleartext values are
+/// injected into the compose invocation's environment.
+pub async fn apply_change(cfg: &AppConfig, change: &PlannedChange) -> anyhow::Result<()> {
+    let compose_dir = Path::new(&change.path)
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("compose file has no parent directory"))?;
+    let secrets = sops::decrypt_stack_env(cfg, compose_dir);
+    if secrets.status == DecryptStatus::Failed {
+        tracing::warn!(stack = %change.stack, "sops decryption failed, applying without decrypted env");
+    }
+
+    let mut cmd = tokio::process::Command::new("docker");
+    cmd.current_dir(compose_dir).envs(&secrets.env).arg("compose").arg("-f").arg(&change.path);
+    match change.action {
+        Action::Create | Action::Recreate => {
+            cmd.arg("up").arg("-d").arg("--force-recreate").arg(&change.service);
+        }
+        Action::Remove => {
+            cmd.arg("rm").arg("-f").arg("-s").arg(&change.service);
+        }
+    }
+    let status = cmd.status().await?;
+    if !status.success() {
+        anyhow::bail!("docker compose exited with {status}");
+    }
+    Ok(())
+}
+
+/// Pure diff: for each desired service, decide whether it needs to be
+/// created (no matching running container), recreated (stopped, or
+/// running a different image than the compose file declares), or left
+/// alone; then flags any running container whose `service` label isn't
+/// in the desired set for removal. Matches on the `com.docker.compose.service`
+/// label rather than the container name, since Docker's generated
+/// container names (`<project>-<service>-<n>`) never equal the bare
+/// service name.
+fn diff_services(desired: &[DesiredService], running: &[Container]) -> Vec<(String, Action)> {
+    let mut changes = Vec::new();
+    let desired_names: BTreeSet<&str> = desired.iter().map(|s| s.name.as_str()).collect();
+
+    for service in desired {
+        let action = match running.iter().find(|c| c.service == service.name) {
+            None => Some(Action::Create),
+            Some(c) if c.state != "running" => Some(Action::Recreate),
+            Some(c) if c.image != service.image => Some(Action::Recreate),
+            Some(_) => None,
+        };
+        if let Some(action) = action {
+            changes.push((service.name.clone(), action));
+        }
+    }
+    for extra in running.iter().filter(|c| !desired_names.contains(c.service.as_str())) {
+        changes.push((extra.service.clone(), Action::Remove));
+    }
+    changes
+}
+
+fn resolve_compose_file(stack_dir: &Path) -> Option<PathBuf> {
+    let tpl = stack_dir.join("docker-compose.tpl.yaml");
+    if tpl.exists() {
+        return Some(tpl);
+    }
+    let plain = stack_dir.join("docker-compose.yaml");
+    if plain.exists() {
+        return Some(plain);
+    }
+    None
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DesiredService {
+    name: String,
+    image: String,
+}
+
+/// Renders `${VAR}` / `${VAR:-default}` placeholders from the process
+/// environment and returns each top-level service's name and declared
+/// image (used to detect image/config drift against what's running).
+fn desired_services(compose_path: &Path) -> anyhow::Result<Vec<DesiredService>> {
+    let raw = fs::read_to_string(compose_path)?;
+    let rendered = render_template(&raw);
+    let doc: serde_yaml::Value = serde_yaml::from_str(&rendered)?;
+    let services = doc
+        .get("services")
+        .and_then(|v| v.as_mapping())
+        .map(|m| {
+            m.iter()
+                .filter_map(|(k, v)| {
+                    let name = k.as_str()?.to_string();
+                    let image = v.get("image").and_then(|i| i.as_str()).unwrap_or_default().to_string();
+                    Some(DesiredService { name, image })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(services)
+}
+
+fn render_template(input: &str) -> String {
+    let re = regex::Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(:-([^}]*))?\}").unwrap();
+    re.replace_all(input, |caps: &regex::Captures| {
+        let name = &caps[1];
+        std::env::var(name).unwrap_or_else(|_| caps.get(3).map(|m| m.as_str()).unwrap_or("").to_string())
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(name: &str, service: &str, image: &str, state: &str) -> Container {
+        Container {
+            name: name.to_string(),
+            service: service.to_string(),
+            image: image.to_string(),
+            state: state.to_string(),
+        }
+    }
+
+    fn service(name: &str, image: &str) -> DesiredService {
+        DesiredService { name: name.to_string(), image: image.to_string() }
+    }
+
+    #[test]
+    fn diff_matches_on_service_label_not_container_name() {
+        let desired = vec![service("web", "nginx:1.27")];
+        let running = vec![container("myproj-web-1", "web", "nginx:1.27", "running")];
+        assert!(diff_services(&desired, &running).is_empty());
+    }
+
+    #[test]
+    fn diff_creates_missing_service() {
+        let desired = vec![service("web", "nginx:1.27")];
+        let running = vec![];
+        assert_eq!(diff_services(&desired, &running), vec![("web".to_string(), Action::Create)]);
+    }
+
+    #[test]
+    fn diff_recreates_on_image_drift() {
+        let desired = vec![service("web", "nginx:1.28")];
+        let running = vec![container("myproj-web-1", "web", "nginx:1.27", "running")];
+        assert_eq!(diff_services(&desired, &running), vec![("web".to_string(), Action::Recreate)]);
+    }
+
+    #[test]
+    fn diff_recreates_stopped_service() {
+        let desired = vec![service("web", "nginx:1.27")];
+        let running = vec![container("myproj-web-1", "web", "nginx:1.27", "exited")];
+        assert_eq!(diff_services(&desired, &running), vec![("web".to_string(), Action::Recreate)]);
+    }
+
+    #[test]
+    fn diff_removes_orphaned_container() {
+        let desired = vec![];
+        let running = vec![container("myproj-worker-1", "worker", "redis:7", "running")];
+        assert_eq!(diff_services(&desired, &running), vec![("worker".to_string(), Action::Remove)]);
+    }
+
+    #[test]
+    fn render_template_substitutes_env_and_default() {
+        std::env::set_var("DDUI_TEST_RECONCILE_VAR", "beta");
+        let out = render_template("image: app:${DDUI_TEST_RECONCILE_VAR}\nport: ${DDUI_TEST_RECONCILE_MISSING:-8080}");
+        assert_eq!(out, "image: app:beta\nport: 8080");
+        std::env::remove_var("DDUI_TEST_RECONCILE_VAR");
+    }
+}
@@ -0,0 +1,105 @@
+// src/reload.rs
+//! Background hot-reload for `Entitlements`. Watches the license file for
+//! changes and listens for `SIGHUP`, revalidates the freshly loaded
+//! value, and atomically swaps it into `AppState` so in-flight requests
+//! are unaffected and the next request sees the new snapshot.
+//!
+//! `AppConfig` is deliberately *not* re-read here: it's sourced entirely
+//! from `AppConfig::from_env`, and process environment variables cannot
+//! change for an already-running process — re-calling `from_env` on
+//! SIGHUP would just reconstruct the same values every time, which looks
+//! like hot-reload but silently never takes effect. Config fields that
+//! need to change without a restart (e.g. `scan_root`) would need a real
+//! reloadable source — a config file, watched the same way the license
+//! file is here — which is out of scope for this commit.
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{api::AppState, entitlements::Entitlements};
+
+/// Spawns the watcher task. Runs for the lifetime of the process.
+pub fn spawn(state: AppState) {
+    tokio::spawn(watch_license_file(state.clone()));
+    tokio::spawn(watch_sighup(state));
+}
+
+async fn watch_sighup(state: AppState) {
+    let mut stream = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to install SIGHUP handler, hot-reload via signal disabled");
+            return;
+        }
+    };
+    while stream.recv().await.is_some() {
+        tracing::info!("SIGHUP received, reloading entitlements");
+        reload(&state);
+    }
+}
+
+/// Watches the *parent directory* of the license file rather than the
+/// file itself, filtering events down to ones naming it. Watching the
+/// file path directly only catches in-place writes: an atomic-replace
+/// update (temp-file + rename onto the license path) unlinks the inode
+/// notify is watching, which silently ends delivery for that path
+/// forever. Watching the directory survives that. Because events are
+/// filtered by the license file's own name, an update that only renames
+/// a different entry (such as a Kubernetes Secret/ConfigMap volume's
+/// `..data` symlink swap) does not trigger a reload; SIGHUP still does.
+async fn watch_license_file(state: AppState) {
+    let path = state.cfg.load().license_path.clone();
+    let Some(parent) = Path::new(&path).parent().filter(|p| !p.as_os_str().is_empty()) else {
+        tracing::warn!(%path, "license path has no parent directory, hot-reload via filesystem disabled");
+        return;
+    };
+    let file_name = Path::new(&path).file_name().map(|n| n.to_os_string());
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher: RecommendedWatcher = match notify::recommended_watcher(move |res| {
+        let _ = tx.send(res);
+    }) {
+        Ok(w) => w,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to create file watcher, hot-reload via filesystem disabled");
+            return;
+        }
+    };
+    if let Err(err) = watcher.watch(parent, RecursiveMode::NonRecursive) {
+        tracing::warn!(%path, error = %err, "failed to watch license directory, hot-reload via filesystem disabled");
+        return;
+    }
+    while let Some(event) = rx.recv().await {
+        match event {
+            Ok(event) => {
+                let affects_license = event.paths.iter().any(|p| p.file_name() == file_name.as_deref());
+                if affects_license {
+                    tracing::info!(%path, "license file changed, reloading entitlements");
+                    reload(&state);
+                }
+            }
+            Err(err) => tracing::warn!(error = %err, "file watcher error"),
+        }
+    }
+}
+
+fn reload(state: &AppState) {
+    let old_ents = state.ents.load_full();
+
+    match Entitlements::load() {
+        Ok(new_ents) => {
+            if new_ents.edition != old_ents.edition {
+                tracing::info!(from = %old_ents.edition, to = %new_ents.edition, "entitlements edition changed");
+            }
+            if new_ents.max_hosts != old_ents.max_hosts {
+                tracing::info!(old = ?old_ents.max_hosts, new = ?new_ents.max_hosts, "max_hosts changed");
+            }
+            if new_ents.features.ci_api != old_ents.features.ci_api {
+                tracing::info!(enabled = new_ents.features.ci_api, "ci_api feature toggled");
+            }
+            state.ents.store(std::sync::Arc::new(new_ents));
+        }
+        Err(err) => tracing::warn!(error = %err, "new entitlements failed to parse, keeping previous entitlements"),
+    }
+}
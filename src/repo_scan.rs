@@ -0,0 +1,100 @@
+// src/repo_scan.rs
+//! Git-backed scan source for `DDUI_SCAN_KIND=repo`. Treats `scan_root`
+//! as a remote URL: clones it into `scan_cache_dir` on startup, pulls on
+//! the `refresh_interval` cadence, and hands back the checked-out
+//! working tree path plus the resolved commit SHA so `inventory.rs` can
+//! scan it exactly like a local directory.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use git2::{Cred, FetchOptions, RemoteCallbacks, Repository};
+
+use crate::config::AppConfig;
+
+/// Shared handle to the last-resolved commit SHA of the tracked repo.
+/// `None` until the first successful clone/pull.
+pub type RepoSha = Arc<ArcSwap<Option<String>>>;
+
+pub fn worktree_path(cfg: &AppConfig) -> PathBuf {
+    Path::new(&cfg.scan_cache_dir).join("worktree")
+}
+
+/// Clones the repo if it isn't present, otherwise fetches and hard-resets
+/// to `origin/<scan_ref>`. Returns the resolved commit SHA on success.
+/// Errors are the caller's to log via tracing; they never panic the
+/// scan loop.
+pub async fn sync(cfg: &AppConfig) -> anyhow::Result<String> {
+    let cfg = cfg.clone();
+    tokio::task::spawn_blocking(move || sync_blocking(&cfg)).await?
+}
+
+fn sync_blocking(cfg: &AppConfig) -> anyhow::Result<String> {
+    let path = worktree_path(cfg);
+    let repo = if path.join(".git").exists() {
+        Repository::open(&path)?
+    } else {
+        std::fs::create_dir_all(&path)?;
+        let mut builder = git2::build::RepoBuilder::new();
+        builder.fetch_options(fetch_options(cfg));
+        builder.clone(&cfg.scan_root, &path)?
+    };
+
+    let mut remote = repo.find_remote("origin")?;
+    remote.fetch(&[&cfg.scan_ref], Some(&mut fetch_options(cfg)), None)?;
+
+    let fetch_head = repo.find_reference("FETCH_HEAD")?;
+    let commit = repo.reference_to_annotated_commit(&fetch_head)?;
+    let object = repo.find_object(commit.id(), None)?;
+    repo.reset(&object, git2::ResetType::Hard, None)?;
+
+    Ok(commit.id().to_string())
+}
+
+fn fetch_options(cfg: &AppConfig) -> FetchOptions<'static> {
+    let token = cfg.scan_git_token.clone();
+    let ssh_key = cfg.scan_git_ssh_key.clone();
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |_url, username_from_url, _allowed| {
+        if let Some(token) = &token {
+            return Cred::userpass_plaintext("x-access-token", token);
+        }
+        if let Some(key_path) = &ssh_key {
+            return Cred::ssh_key(username_from_url.unwrap_or("git"), None, Path::new(key_path), None);
+        }
+        Cred::default()
+    });
+
+    let mut opts = FetchOptions::new();
+    opts.remote_callbacks(callbacks);
+    opts
+}
+
+/// Runs `sync` once at startup, then again every `cfg.refresh_interval`,
+/// re-reading `cfg` from its `ArcSwap` on every iteration. `reload.rs`
+/// only swaps entitlements, so in practice each iteration sees the
+/// `DDUI_SCAN_ROOT`/`DDUI_SCAN_REF`/git credential values read at
+/// startup; changing them needs a restart. Stores the resolved SHA into
+/// `repo_sha` so `/api/healthz` can report it. Sync failures are logged
+/// and retried on the next tick rather than crashing the server.
+pub fn spawn(cfg: Arc<ArcSwap<AppConfig>>, repo_sha: RepoSha) {
+    tokio::spawn(async move {
+        loop {
+            let snapshot = cfg.load_full();
+            if snapshot.scan_kind == "repo" {
+                match sync(&snapshot).await {
+                    Ok(sha) => {
+                        tracing::info!(%sha, remote = %snapshot.scan_root, "repo scan source synced");
+                        repo_sha.store(Arc::new(Some(sha)));
+                    }
+                    Err(err) => tracing::warn!(error = %err, remote = %snapshot.scan_root, "failed to sync repo scan source"),
+                }
+            }
+            let interval = humantime::parse_duration(&snapshot.refresh_interval)
+                .unwrap_or(std::time::Duration::from_secs(600));
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
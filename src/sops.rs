@@ -0,0 +1,465 @@
+// src/sops.rs
+//! Opt-in decryption of a stack's `.env.sops` / `.sops.*` files, gated by
+//! `DDUI_SOPS_ENABLE`. Covers the common case: a YAML or dotenv document
+//! with a `sops` metadata block describing `age` recipients and a MAC
+//! over the cleartext values. PGP recipients are decrypted by shelling
+//! out to `gpg`, the same way the reconciler shells out to `docker
+//! compose` rather than embedding an OpenPGP implementation.
+//!
+//! Decrypted values are only ever handed to the reconciler in-process;
+//! nothing in this module logs them, only key counts and status.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use age::secrecy::ExposeSecret;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use sha2::{Digest, Sha512};
+
+use crate::config::AppConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecryptStatus {
+    Success,
+    Failed,
+    Skipped,
+}
+
+pub struct Decrypted {
+    pub status: DecryptStatus,
+    pub env: BTreeMap<String, String>,
+    pub keys_loaded: usize,
+}
+
+impl Decrypted {
+    fn skipped() -> Self {
+        Self { status: DecryptStatus::Skipped, env: BTreeMap::new(), keys_loaded: 0 }
+    }
+    fn failed() -> Self {
+        Self { status: DecryptStatus::Failed, env: BTreeMap::new(), keys_loaded: 0 }
+    }
+}
+
+/// Cheaply reports whether `stack_dir`'s SOPS file is likely decryptable
+/// with this node's configured key material, *without* performing the
+/// actual AES-256-GCM decrypt/MAC-verify or (for PGP) spawning `gpg` —
+/// that real decrypt only ever happens in `reconcile::apply_change`.
+/// Intended for `/api/inventory`, which is polled far more often than a
+/// plan/apply run and shouldn't materialize cleartext secrets or block a
+/// worker thread on a `gpg` child process just to render a status badge.
+///
+/// For an `age` recipient this confirms the configured identity's public
+/// key matches one of the file's recipient stanzas. For a PGP-only
+/// stack there's no equivalently cheap check (confirming a `gpg` key
+/// match still requires invoking `gpg`), so this reports `Failed` and
+/// leaves the real answer to apply time.
+pub fn check_stack_status(cfg: &AppConfig, stack_dir: &Path) -> DecryptStatus {
+    if !cfg.sops_enable {
+        return DecryptStatus::Skipped;
+    }
+    let Some(path) = find_sops_file(stack_dir) else {
+        return DecryptStatus::Skipped;
+    };
+    match check_file_status(cfg, &path) {
+        Ok(status) => status,
+        Err(err) => {
+            tracing::warn!(path = %path.display(), error = %err, "sops status check failed");
+            DecryptStatus::Failed
+        }
+    }
+}
+
+fn check_file_status(cfg: &AppConfig, path: &Path) -> Result<DecryptStatus> {
+    let raw = std::fs::read_to_string(path)?;
+    let (_entries, sops_meta) = parse_document(&raw)?;
+
+    let Some(age_stanzas) = sops_meta.get("age").and_then(|v| v.as_sequence()) else {
+        return Ok(DecryptStatus::Failed);
+    };
+    let Some(identity) = load_age_identity(cfg)? else {
+        return Ok(DecryptStatus::Failed);
+    };
+    let our_recipient = identity.to_public().to_string();
+    let matches = age_stanzas
+        .iter()
+        .filter_map(|s| s.get("recipient").and_then(|r| r.as_str()))
+        .any(|r| r == our_recipient);
+    Ok(if matches { DecryptStatus::Success } else { DecryptStatus::Failed })
+}
+
+/// Finds and decrypts the first `.env.sops` / `.sops.*` file in
+/// `stack_dir`. Returns `Skipped` when the feature is disabled or the
+/// stack has no SOPS file, `Failed` (with no partial values) when key
+/// material is missing or the MAC doesn't verify, never panicking and
+/// never logging decrypted content.
+pub fn decrypt_stack_env(cfg: &AppConfig, stack_dir: &Path) -> Decrypted {
+    if !cfg.sops_enable {
+        return Decrypted::skipped();
+    }
+    let Some(path) = find_sops_file(stack_dir) else {
+        return Decrypted::skipped();
+    };
+    match decrypt_file(cfg, &path) {
+        Ok(decrypted) => decrypted,
+        Err(err) => {
+            tracing::warn!(path = %path.display(), error = %err, "sops decryption failed");
+            Decrypted::failed()
+        }
+    }
+}
+
+fn find_sops_file(dir: &Path) -> Option<std::path::PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    for entry in entries.flatten() {
+        if !entry.path().is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if name.ends_with(".env.sops") || name.contains(".sops.") {
+            return Some(entry.path());
+        }
+    }
+    None
+}
+
+fn decrypt_file(cfg: &AppConfig, path: &Path) -> Result<Decrypted> {
+    let raw = std::fs::read_to_string(path)?;
+    let (entries, sops_meta) = parse_document(&raw)?;
+
+    let data_key = unwrap_data_key(cfg, &sops_meta)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+
+    let mut env = BTreeMap::new();
+    let mut cleartext_for_mac = String::new();
+    for (key, enc) in &entries {
+        let value = decrypt_value(&cipher, enc, key)?;
+        cleartext_for_mac.push_str(&value);
+        env.insert(key.clone(), value);
+    }
+
+    let expected_mac = sops_meta
+        .get("mac")
+        .and_then(|m| m.as_str())
+        .ok_or_else(|| anyhow!("missing sops mac"))?;
+    let mac_cleartext = decrypt_value(&cipher, expected_mac, "")?;
+    let computed = hex::encode_upper(Sha512::digest(cleartext_for_mac.as_bytes()));
+    if !mac_cleartext.eq_ignore_ascii_case(&computed) {
+        anyhow::bail!("mac verification failed");
+    }
+
+    let keys_loaded = env.len();
+    Ok(Decrypted { status: DecryptStatus::Success, env, keys_loaded })
+}
+
+/// Parses either a YAML/JSON sops document (a `sops:` metadata map
+/// alongside the encrypted top-level keys) or a `.env.sops` dotenv
+/// document (`KEY=ENC[...]` lines plus `sops_*` metadata lines), and
+/// normalizes both into a flat list of `(key, ENC[...] token)` pairs and
+/// a `sops` metadata value shaped like the YAML form so the rest of this
+/// module doesn't need to care which format it read.
+fn parse_document(raw: &str) -> Result<(Vec<(String, String)>, serde_yaml::Value)> {
+    if let Ok(doc) = serde_yaml::from_str::<serde_yaml::Value>(raw) {
+        if let Some(sops_meta) = doc.get("sops") {
+            let mut entries = Vec::new();
+            if let Some(mapping) = doc.as_mapping() {
+                for (k, v) in mapping {
+                    let Some(key) = k.as_str() else { continue };
+                    if key == "sops" {
+                        continue;
+                    }
+                    let Some(enc) = v.as_str() else { continue };
+                    entries.push((key.to_string(), enc.to_string()));
+                }
+            }
+            return Ok((entries, sops_meta.clone()));
+        }
+    }
+    parse_dotenv(raw)
+}
+
+fn parse_dotenv(raw: &str) -> Result<(Vec<(String, String)>, serde_yaml::Value)> {
+    let age_re = Regex::new(r"^sops_age__list_(\d+)__map_(recipient|enc)$").unwrap();
+    let pgp_re = Regex::new(r"^sops_pgp__list_(\d+)__map_(fp|enc)$").unwrap();
+
+    let mut entries = Vec::new();
+    let mut age_enc: BTreeMap<usize, String> = BTreeMap::new();
+    let mut age_recipient: BTreeMap<usize, String> = BTreeMap::new();
+    let mut pgp_enc: BTreeMap<usize, String> = BTreeMap::new();
+    let mut mac = None;
+
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let key = key.trim();
+        let value = unescape_dotenv(value.trim());
+
+        if key == "sops_mac" {
+            mac = Some(value);
+        } else if let Some(caps) = age_re.captures(key) {
+            let idx = caps[1].parse().unwrap_or(0);
+            match &caps[2] {
+                "enc" => { age_enc.insert(idx, value); }
+                "recipient" => { age_recipient.insert(idx, value); }
+                _ => {}
+            }
+        } else if let Some(caps) = pgp_re.captures(key) {
+            if &caps[2] == "enc" {
+                pgp_enc.insert(caps[1].parse().unwrap_or(0), value);
+            }
+        } else if key == "sops_version" {
+            continue;
+        } else if !key.starts_with("sops_") {
+            entries.push((key.to_string(), value));
+        }
+    }
+
+    let mac = mac.ok_or_else(|| anyhow!("missing sops_mac in dotenv document"))?;
+    let mut sops_meta = serde_yaml::Mapping::new();
+    sops_meta.insert("mac".into(), mac.into());
+    if !age_enc.is_empty() {
+        sops_meta.insert("age".into(), serde_yaml::Value::Sequence(to_enc_sequence(age_enc, &age_recipient)));
+    }
+    if !pgp_enc.is_empty() {
+        sops_meta.insert("pgp".into(), serde_yaml::Value::Sequence(to_enc_sequence(pgp_enc, &BTreeMap::new())));
+    }
+    Ok((entries, serde_yaml::Value::Mapping(sops_meta)))
+}
+
+fn to_enc_sequence(entries: BTreeMap<usize, String>, recipients: &BTreeMap<usize, String>) -> Vec<serde_yaml::Value> {
+    entries
+        .into_iter()
+        .map(|(idx, enc)| {
+            let mut m = serde_yaml::Mapping::new();
+            m.insert("enc".into(), enc.into());
+            if let Some(recipient) = recipients.get(&idx) {
+                m.insert("recipient".into(), recipient.clone().into());
+            }
+            serde_yaml::Value::Mapping(m)
+        })
+        .collect()
+}
+
+/// The dotenv store escapes embedded newlines as literal `\n` since env
+/// values can't contain real ones.
+fn unescape_dotenv(value: &str) -> String {
+    value.replace("\\n", "\n")
+}
+
+/// Unwraps the per-file data key from whichever recipient stanza this
+/// key material can satisfy: the node's `age` identity, falling back to
+/// `gpg --decrypt` for a `pgp` recipient.
+fn unwrap_data_key(cfg: &AppConfig, sops_meta: &serde_yaml::Value) -> Result<[u8; 32]> {
+    if let Some(age_stanzas) = sops_meta.get("age").and_then(|v| v.as_sequence()) {
+        if let Some(identity) = load_age_identity(cfg)? {
+            for stanza in age_stanzas {
+                let Some(enc) = stanza.get("enc").and_then(|v| v.as_str()) else { continue };
+                if let Ok(key) = unwrap_age(enc, &identity) {
+                    return Ok(key);
+                }
+            }
+        }
+    }
+    if let Some(pgp_stanzas) = sops_meta.get("pgp").and_then(|v| v.as_sequence()) {
+        for stanza in pgp_stanzas {
+            let Some(enc) = stanza.get("enc").and_then(|v| v.as_str()) else { continue };
+            if let Ok(key) = unwrap_pgp(enc) {
+                return Ok(key);
+            }
+        }
+    }
+    Err(anyhow!("no usable age or pgp key material could unwrap the data key"))
+}
+
+fn load_age_identity(cfg: &AppConfig) -> Result<Option<age::x25519::Identity>> {
+    let raw = if let Some(key) = &cfg.sops_age_key {
+        key.clone()
+    } else if let Some(path) = &cfg.sops_age_key_file {
+        std::fs::read_to_string(path)?
+    } else {
+        return Ok(None);
+    };
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Ok(identity) = age::x25519::Identity::from_str(line) {
+            return Ok(Some(identity));
+        }
+    }
+    Err(anyhow!("no parseable age identity found in key material"))
+}
+
+fn unwrap_age(enc: &str, identity: &age::x25519::Identity) -> Result<[u8; 32]> {
+    let decryptor = age::Decryptor::new(enc.as_bytes())?;
+    let age::Decryptor::Recipients(decryptor) = decryptor else {
+        anyhow::bail!("sops age stanza is not a recipients-style age message");
+    };
+    let mut out = Vec::new();
+    let mut reader = decryptor.decrypt(std::iter::once(identity as &dyn age::Identity))?;
+    std::io::Read::read_to_end(&mut reader, &mut out)?;
+    out.try_into().map_err(|_| anyhow!("unwrapped age data key was not 32 bytes"))
+}
+
+/// Shells out to `gpg` to unwrap a PGP-wrapped data key, mirroring how
+/// `reconcile.rs` shells out to `docker compose` rather than vendoring a
+/// full protocol implementation.
+fn unwrap_pgp(enc: &str) -> Result<[u8; 32]> {
+    use std::io::Write;
+    let mut child = std::process::Command::new("gpg")
+        .arg("--decrypt")
+        .arg("--quiet")
+        .arg("--batch")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .context("failed to spawn gpg")?;
+    child.stdin.take().unwrap().write_all(enc.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("gpg exited with {}", output.status);
+    }
+    output
+        .stdout
+        .try_into()
+        .map_err(|_| anyhow!("unwrapped pgp data key was not 32 bytes"))
+}
+
+/// Parses a single `ENC[AES256_GCM,data:<b64>,iv:<b64>,tag:<b64>,type:str]`
+/// token and decrypts it with the unwrapped data key. `path` is the
+/// value's tree path (e.g. `DATABASE_URL`); upstream sops binds each
+/// value's ciphertext to `<path>:<type>` as additional authenticated
+/// data (the token's own `type:` suffix, not a caller-supplied one) so
+/// ciphertexts can't be rearranged between keys or reinterpreted as a
+/// different type. Pass `""` for the `sops.mac` value itself, which sops
+/// always decrypts with empty AAD regardless of type.
+///
+/// CAVEAT: this has only been round-tripped against this module's own
+/// `encrypt_token` test helper, not verified against a real `sops
+/// age`-encrypted fixture — if upstream's exact path/type join differs
+/// (e.g. a version number folded in, or nested-tree path separators),
+/// decrypting a genuine SOPS file will fail with "aes-gcm decryption
+/// failed" below rather than silently producing wrong plaintext, but it
+/// still needs that verification before relying on it in the wild. This
+/// subset only covers flat (single-level) documents, where the path is
+/// just the key name — nested trees use a colon-joined path this
+/// doesn't reconstruct.
+fn decrypt_value(cipher: &Aes256Gcm, enc: &str, path: &str) -> Result<String> {
+    let re = Regex::new(
+        r"^ENC\[AES256_GCM,data:(?P<data>[^,]*),iv:(?P<iv>[^,]*),tag:(?P<tag>[^,]*),type:(?P<type>\w+)\]$",
+    )
+    .unwrap();
+    let caps = re.captures(enc).ok_or_else(|| anyhow!("value is not a sops ENC[] token"))?;
+    let data = base64::decode(&caps["data"])?;
+    let iv = base64::decode(&caps["iv"])?;
+    let tag = base64::decode(&caps["tag"])?;
+
+    let mut ciphertext_and_tag = data;
+    ciphertext_and_tag.extend_from_slice(&tag);
+    if iv.len() != 12 {
+        anyhow::bail!("sops iv must be 12 bytes, got {}", iv.len());
+    }
+    let nonce = Nonce::from_slice(&iv);
+    let aad = if path.is_empty() { String::new() } else { format!("{path}:{}", &caps["type"]) };
+    let plaintext = cipher
+        .decrypt(nonce, Payload { msg: &ciphertext_and_tag, aad: aad.as_bytes() })
+        .map_err(|_| anyhow!("aes-gcm decryption failed"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mirrors `decrypt_value`'s `<path>:<type>` AAD construction (this
+    /// helper always emits `type:str`) so the round-trip tests exercise
+    /// the same binding real SOPS files are expected to use.
+    fn encrypt_token(cipher: &Aes256Gcm, plaintext: &str, path: &str) -> String {
+        let iv = [1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let nonce = Nonce::from_slice(&iv);
+        let aad = if path.is_empty() { String::new() } else { format!("{path}:str") };
+        let mut ciphertext = cipher
+            .encrypt(nonce, Payload { msg: plaintext.as_bytes(), aad: aad.as_bytes() })
+            .unwrap();
+        let tag = ciphertext.split_off(ciphertext.len() - 16);
+        format!(
+            "ENC[AES256_GCM,data:{},iv:{},tag:{},type:str]",
+            base64::encode(&ciphertext),
+            base64::encode(iv),
+            base64::encode(&tag),
+        )
+    }
+
+    fn test_cipher() -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&[7u8; 32]))
+    }
+
+    #[test]
+    fn decrypt_value_round_trips_with_matching_aad() {
+        let cipher = test_cipher();
+        let token = encrypt_token(&cipher, "hunter2", "DATABASE_PASSWORD");
+        assert_eq!(decrypt_value(&cipher, &token, "DATABASE_PASSWORD").unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn decrypt_value_rejects_mismatched_aad() {
+        let cipher = test_cipher();
+        let token = encrypt_token(&cipher, "hunter2", "DATABASE_PASSWORD");
+        assert!(decrypt_value(&cipher, &token, "OTHER_KEY").is_err());
+    }
+
+    #[test]
+    fn decrypt_value_rejects_malformed_token_without_panicking() {
+        let cipher = test_cipher();
+        assert!(decrypt_value(&cipher, "not a sops token", "KEY").is_err());
+    }
+
+    #[test]
+    fn decrypt_value_rejects_short_iv_without_panicking() {
+        let cipher = test_cipher();
+        let token = "ENC[AES256_GCM,data:YWJj,iv:YWI=,tag:dGFn,type:str]";
+        assert!(decrypt_value(&cipher, token, "KEY").is_err());
+    }
+
+    #[test]
+    fn parse_dotenv_captures_age_recipient_for_status_checks() {
+        let raw = "\
+DATABASE_URL=ENC[AES256_GCM,data:YWJj,iv:MTIzNDU2Nzg5MDEy,tag:dGFndGFndGFndGFn,type:str]
+sops_age__list_0__map_recipient=age1exampleexampleexampleexampleexampleexampleexampleexample
+sops_age__list_0__map_enc=-----BEGIN AGE ENCRYPTED FILE-----\\nabc\\n-----END AGE ENCRYPTED FILE-----
+sops_mac=ENC[AES256_GCM,data:bWFj,iv:MTIzNDU2Nzg5MDEy,tag:dGFndGFndGFndGFn,type:str]
+";
+        let (_, meta) = parse_dotenv(raw).unwrap();
+        let age = meta.get("age").unwrap().as_sequence().unwrap();
+        assert_eq!(
+            age[0].get("recipient").unwrap().as_str().unwrap(),
+            "age1exampleexampleexampleexampleexampleexampleexampleexample"
+        );
+    }
+
+    #[test]
+    fn parse_dotenv_extracts_entries_and_age_recipients() {
+        let raw = "\
+DATABASE_URL=ENC[AES256_GCM,data:YWJj,iv:MTIzNDU2Nzg5MDEy,tag:dGFndGFndGFndGFn,type:str]
+sops_age__list_0__map_recipient=age1exampleexampleexampleexampleexampleexampleexampleexample
+sops_age__list_0__map_enc=-----BEGIN AGE ENCRYPTED FILE-----\\nabc\\n-----END AGE ENCRYPTED FILE-----
+sops_mac=ENC[AES256_GCM,data:bWFj,iv:MTIzNDU2Nzg5MDEy,tag:dGFndGFndGFndGFn,type:str]
+sops_version=3.8.1
+";
+        let (entries, meta) = parse_dotenv(raw).unwrap();
+        assert_eq!(entries, vec![("DATABASE_URL".to_string(), "ENC[AES256_GCM,data:YWJj,iv:MTIzNDU2Nzg5MDEy,tag:dGFndGFndGFndGFn,type:str]".to_string())]);
+        assert!(meta.get("mac").is_some());
+        let age = meta.get("age").unwrap().as_sequence().unwrap();
+        assert_eq!(age.len(), 1);
+        assert!(age[0].get("enc").unwrap().as_str().unwrap().contains("BEGIN AGE ENCRYPTED FILE"));
+    }
+}